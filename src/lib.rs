@@ -13,7 +13,16 @@
 //!
 //! ## Retrieving Services
 //!
-//! To retrieve all the services, simply use `cf_services::get_services_from_env`.
+//! To retrieve all the services, simply use `cf_services::get_services_from_env`. If the services
+//! document is sourced from somewhere else, such as a mounted config file or an in-memory string
+//! (common in tests), use `cf_services::get_services_from_file` or
+//! `cf_services::get_services_from_str` instead.
+//!
+//! ## YAML
+//!
+//! With the `yaml` feature enabled, `cf_services::get_services_from_file` also accepts a
+//! `.yaml`/`.yml` services manifest, and `cf_services::get_services_from_yaml_str` can parse one
+//! directly from a string. Both deserialize into the same `Service`/`Credentials` model as JSON.
 //!
 //! ## Service Credential
 //!
@@ -21,10 +30,12 @@
 //! `cf_services::get_service_cred_from_env` or the convenience function
 //! `cf_services::get_service_credentials`.
 
-use std::{env, fmt};
+use std::{env, fmt, fs};
 use std::collections::HashMap;
 use std::fmt::Formatter;
+use std::path::Path;
 
+use serde::de::DeserializeOwned;
 use serde::Deserialize;
 
 /// The environment variable key that contains all the bounded services to the application.
@@ -43,11 +54,21 @@ pub struct Service {
     /// The name the service is bounded as.
     #[serde(default)]
     pub binding_name: String,
-    /// The credentials of the service.
-    pub credentials: Credentials,
+    /// The credentials of the service, kept as a raw JSON value so it can be deserialized into
+    /// either [`Credentials`] or a caller-supplied type.
+    pub credentials: serde_json::Value,
     /// The label associated with the service.
     #[serde(default)]
     pub label: String,
+    /// The tags associated with the service.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// The plan of the service.
+    #[serde(default)]
+    pub plan: String,
+    /// The provider of the service.
+    #[serde(default)]
+    pub provider: String,
 }
 
 /// The credentials information for authenticating with the service.
@@ -92,6 +113,10 @@ pub struct Credentials {
     /// The name of the credentials.
     #[serde(default)]
     pub name: String,
+    /// Any provider-specific credential fields not already captured above (e.g. `database`,
+    /// `vhost`, `tls_ca`).
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
 }
 
 /// Retrieves the credential information of the specified service.
@@ -103,36 +128,149 @@ pub fn get_service_cred_from_env(service_name: String) -> Result<Vec<Credentials
 /// Retrieves all service information.
 pub fn get_services_from_env() -> Result<HashMap<String, Vec<Service>>, CFError> {
     env::var(VCAP_SERVICES)
-        .map_err(|_| CFError::EnvNotSet)
-        .and_then(|val| serde_json::from_str(&val).map_err(|_| CFError::MalformedJSON))
+        .map_err(CFError::EnvNotSet)
+        .and_then(|val| get_services_from_str(&val))
+}
+
+/// Retrieves all service information from the provided JSON string.
+pub fn get_services_from_str(json: &str) -> Result<HashMap<String, Vec<Service>>, CFError> {
+    serde_json::from_str(json).map_err(CFError::MalformedJSON)
+}
+
+/// Retrieves all service information from the services document at the provided path. With the
+/// `yaml` feature enabled, files with a `.yaml`/`.yml` extension are parsed as YAML; everything
+/// else is parsed as JSON.
+pub fn get_services_from_file(path: impl AsRef<Path>) -> Result<HashMap<String, Vec<Service>>, CFError> {
+    let path = path.as_ref();
+    let contents = fs::read_to_string(path).map_err(CFError::IoError)?;
+
+    #[cfg(feature = "yaml")]
+    {
+        let is_yaml = matches!(
+            path.extension().and_then(|ext| ext.to_str()),
+            Some("yaml") | Some("yml")
+        );
+        if is_yaml {
+            return get_services_from_yaml_str(&contents);
+        }
+    }
+
+    get_services_from_str(&contents)
+}
+
+/// Retrieves all service information from the provided YAML string.
+#[cfg(feature = "yaml")]
+#[cfg_attr(docsrs, doc(cfg(feature = "yaml")))]
+pub fn get_services_from_yaml_str(yaml: &str) -> Result<HashMap<String, Vec<Service>>, CFError> {
+    serde_yaml::from_str(yaml).map_err(CFError::MalformedYAML)
 }
 
 /// Retrieves the credential information from the provided services that match the specified service
 /// name.
 pub fn get_service_credentials(services: HashMap<String, Vec<Service>>, service_name: String) -> Result<Vec<Credentials>, CFError> {
+    get_typed_service_credentials(services, service_name)
+}
+
+/// Retrieves the credential information of the specified service, deserialized into the
+/// caller-supplied credential type `T` instead of the catch-all [`Credentials`] struct.
+pub fn get_typed_service_cred_from_env<T: DeserializeOwned>(service_name: String) -> Result<Vec<T>, CFError> {
+    get_services_from_env()
+        .and_then(|services| get_typed_service_credentials(services, service_name))
+}
+
+/// Retrieves the credential information from the provided services that match the specified service
+/// name, deserialized into the caller-supplied credential type `T` instead of the catch-all
+/// [`Credentials`] struct.
+pub fn get_typed_service_credentials<T: DeserializeOwned>(services: HashMap<String, Vec<Service>>, service_name: String) -> Result<Vec<T>, CFError> {
     match services.get(&service_name) {
-        Some(services) => Ok(services.iter().map(|service| service.credentials.clone()).collect()),
+        Some(services) => services
+            .iter()
+            .map(|service| serde_json::from_value(service.credentials.clone()).map_err(CFError::MalformedCredentials))
+            .collect(),
         None => Err(CFError::ServiceNotPresent(service_name))
     }
 }
 
+/// Retrieves all services, across every service type, that are tagged with the specified tag.
+pub fn get_services_by_tag<'a>(services: &'a HashMap<String, Vec<Service>>, tag: &str) -> Vec<&'a Service> {
+    services
+        .values()
+        .flatten()
+        .filter(|service| service.tags.iter().any(|t| t == tag))
+        .collect()
+}
+
+/// Retrieves all services, across every service type, that have the specified label.
+pub fn get_services_by_label<'a>(services: &'a HashMap<String, Vec<Service>>, label: &str) -> Vec<&'a Service> {
+    services
+        .values()
+        .flatten()
+        .filter(|service| service.label == label)
+        .collect()
+}
+
 /// Enumeration of the different errors that can occur.
-#[derive(PartialEq, Debug)]
+#[derive(Debug)]
 pub enum CFError {
     /// Error when the environment variable is not set.
-    EnvNotSet,
-    /// Error then the environment variable JSON is malformed.
-    MalformedJSON,
+    EnvNotSet(env::VarError),
+    /// Error when the services document is malformed JSON.
+    MalformedJSON(serde_json::Error),
     /// Error when a service is not present.
     ServiceNotPresent(String),
+    /// Error when a service's credentials don't match the requested credential type.
+    MalformedCredentials(serde_json::Error),
+    /// Error when the services document can't be read from disk.
+    IoError(std::io::Error),
+    /// Error when the YAML services document is malformed. Only available with the `yaml`
+    /// feature.
+    #[cfg(feature = "yaml")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "yaml")))]
+    MalformedYAML(serde_yaml::Error),
 }
 
 impl fmt::Display for CFError {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match *self {
-            CFError::EnvNotSet => write!(f, "environment variable {:?} is not set", VCAP_SERVICES),
-            CFError::MalformedJSON => write!(f, "environment variable {:?} is malformed", VCAP_SERVICES),
-            CFError::ServiceNotPresent(ref s) => write!(f, "service {:?} is not bounded to the application", s)
+            CFError::EnvNotSet(_) => write!(f, "environment variable {:?} is not set", VCAP_SERVICES),
+            CFError::MalformedJSON(_) => write!(f, "services document is malformed JSON"),
+            CFError::ServiceNotPresent(ref s) => write!(f, "service {:?} is not bounded to the application", s),
+            CFError::MalformedCredentials(_) => write!(f, "service credentials do not match the requested type"),
+            CFError::IoError(_) => write!(f, "services document could not be read"),
+            #[cfg(feature = "yaml")]
+            CFError::MalformedYAML(_) => write!(f, "services document is malformed YAML"),
+        }
+    }
+}
+
+impl std::error::Error for CFError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CFError::EnvNotSet(ref e) => Some(e),
+            CFError::MalformedJSON(ref e) => Some(e),
+            CFError::ServiceNotPresent(_) => None,
+            CFError::MalformedCredentials(ref e) => Some(e),
+            CFError::IoError(ref e) => Some(e),
+            #[cfg(feature = "yaml")]
+            CFError::MalformedYAML(ref e) => Some(e),
+        }
+    }
+}
+
+// `serde_json::Error` and `std::io::Error` don't implement `PartialEq`, so equality is reduced to
+// comparing variant discriminants (and the service name for `ServiceNotPresent`); wrapped errors,
+// including the `env::VarError` of `EnvNotSet`, are not compared.
+impl PartialEq for CFError {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (CFError::EnvNotSet(_), CFError::EnvNotSet(_)) => true,
+            (CFError::MalformedJSON(_), CFError::MalformedJSON(_)) => true,
+            (CFError::ServiceNotPresent(a), CFError::ServiceNotPresent(b)) => a == b,
+            (CFError::MalformedCredentials(_), CFError::MalformedCredentials(_)) => true,
+            (CFError::IoError(_), CFError::IoError(_)) => true,
+            #[cfg(feature = "yaml")]
+            (CFError::MalformedYAML(_), CFError::MalformedYAML(_)) => true,
+            _ => false,
         }
     }
 }
@@ -141,8 +279,11 @@ impl fmt::Display for CFError {
 mod tests {
     use std::collections::HashMap;
     use std::env;
+    use std::error::Error;
 
-    use crate::{CFError, Credentials, get_service_cred_from_env, get_service_credentials, get_services_from_env, Service, VCAP_SERVICES};
+    use serde::Deserialize;
+
+    use crate::{CFError, Credentials, get_service_cred_from_env, get_service_credentials, get_services_by_label, get_services_by_tag, get_services_from_env, get_services_from_file, get_services_from_str, get_typed_service_cred_from_env, get_typed_service_credentials, Service, VCAP_SERVICES};
 
     #[test]
     fn test_get_service_cred_from_env() {
@@ -160,7 +301,7 @@ mod tests {
         env::set_var(VCAP_SERVICES, json);
         let creds = get_service_cred_from_env("serviceA".to_string()).unwrap();
         assert_eq!(1, creds.len());
-        let cred = creds.get(0).unwrap();
+        let cred = creds.first().unwrap();
         assert_eq!("example_uri", cred.uri);
         assert_eq!(8080, cred.port);
         env::remove_var(VCAP_SERVICES);
@@ -183,10 +324,10 @@ mod tests {
         let services = get_services_from_env().unwrap();
         let service_a = services.get("serviceA").unwrap();
         assert_eq!(1, service_a.len());
-        let service_details = service_a.get(0).unwrap();
+        let service_details = service_a.first().unwrap();
         assert_eq!("service_a", service_details.name);
-        assert_eq!("example_uri", service_details.credentials.uri);
-        assert_eq!(8080, service_details.credentials.port);
+        assert_eq!("example_uri", service_details.credentials["uri"]);
+        assert_eq!(8080, service_details.credentials["port"]);
         env::remove_var(VCAP_SERVICES);
     }
 
@@ -194,7 +335,16 @@ mod tests {
     fn test_get_services_from_env_not_set() {
         env::remove_var(VCAP_SERVICES);
         let err = get_services_from_env().err().unwrap();
-        assert_eq!(CFError::EnvNotSet, err);
+        assert!(matches!(err, CFError::EnvNotSet(_)));
+    }
+
+    #[test]
+    fn test_get_services_from_env_not_set_preserves_source() {
+        env::remove_var(VCAP_SERVICES);
+        let err = get_services_from_env().err().unwrap();
+        let source = err.source().expect("EnvNotSet should carry the original VarError");
+        let var_error = source.downcast_ref::<env::VarError>().expect("source should be a VarError");
+        assert_eq!(&env::VarError::NotPresent, var_error);
     }
 
     #[test]
@@ -210,8 +360,29 @@ mod tests {
       ]"#;
         env::set_var(VCAP_SERVICES, json);
         let err = get_services_from_env().err().unwrap();
-        assert_eq!(CFError::MalformedJSON, err);
+        assert!(matches!(err, CFError::MalformedJSON(_)));
+        env::remove_var(VCAP_SERVICES);
+    }
+
+    #[test]
+    fn test_get_services_from_env_malformed_json_preserves_source() {
+        let json = r#"{
+      "serviceA": [
+        {
+          "name":"service_a",
+          "credentials": {
+            "uri": "example_uri"
+          }
+        }
+      ]"#;
+        env::set_var(VCAP_SERVICES, json);
+        let err = get_services_from_env().err().unwrap();
         env::remove_var(VCAP_SERVICES);
+
+        let expected = serde_json::from_str::<serde_json::Value>(json).unwrap_err();
+        let source = err.source().expect("MalformedJSON should carry the original serde_json::Error");
+        let json_error = source.downcast_ref::<serde_json::Error>().expect("source should be a serde_json::Error");
+        assert_eq!(expected.to_string(), json_error.to_string());
     }
 
     #[test]
@@ -222,27 +393,17 @@ mod tests {
             name: "service_a".to_string(),
             instance_name: "".to_string(),
             binding_name: "".to_string(),
-            credentials: Credentials {
-                uri: "example_uri".to_string(),
-                jdbc_url: "".to_string(),
-                api_uri: "".to_string(),
-                license_key: "".to_string(),
-                client_secret: "".to_string(),
-                client_id: "".to_string(),
-                access_token_uri: "".to_string(),
-                hostname: "".to_string(),
-                username: "".to_string(),
-                password: "".to_string(),
-                port: 0,
-                name: "".to_string(),
-            },
+            credentials: serde_json::json!({"uri": "example_uri"}),
             label: "".to_string(),
+            tags: Vec::new(),
+            plan: "".to_string(),
+            provider: "".to_string(),
         };
         service_a.push(service);
         services.insert("serviceA".to_string(), service_a);
         let creds = get_service_credentials(services, "serviceA".to_string()).unwrap();
         assert_eq!(1, creds.len());
-        let cred = creds.get(0).unwrap();
+        let cred = creds.first().unwrap();
         assert_eq!("example_uri", cred.uri);
     }
 
@@ -254,4 +415,199 @@ mod tests {
         let err = get_service_credentials(services, "serviceB".to_string()).err().unwrap();
         assert_eq!(CFError::ServiceNotPresent("serviceB".to_string()), err)
     }
+
+    #[test]
+    fn test_get_services_from_env_retains_extra_credential_fields_and_service_metadata() {
+        let json = r#"{
+      "serviceA": [
+        {
+          "name":"service_a",
+          "tags": ["database", "relational"],
+          "plan": "free",
+          "provider": "acme",
+          "credentials": {
+            "uri": "example_uri",
+            "database": "my_db",
+            "vhost": "/"
+          }
+        }
+      ]
+    }"#;
+        env::set_var(VCAP_SERVICES, json);
+        let services = get_services_from_env().unwrap();
+        let service_details = services.get("serviceA").unwrap().first().unwrap();
+        assert_eq!(vec!["database".to_string(), "relational".to_string()], service_details.tags);
+        assert_eq!("free", service_details.plan);
+        assert_eq!("acme", service_details.provider);
+        let creds: Credentials = serde_json::from_value(service_details.credentials.clone()).unwrap();
+        assert_eq!("my_db", creds.extra.get("database").unwrap());
+        assert_eq!("/", creds.extra.get("vhost").unwrap());
+        env::remove_var(VCAP_SERVICES);
+    }
+
+    #[test]
+    fn test_get_services_by_tag() {
+        let json = r#"{
+      "serviceA": [
+        {"name":"service_a", "tags": ["database"], "credentials": {"uri": "example_uri"}}
+      ],
+      "serviceB": [
+        {"name":"service_b", "tags": ["cache"], "credentials": {"uri": "other_uri"}}
+      ]
+    }"#;
+        env::set_var(VCAP_SERVICES, json);
+        let services = get_services_from_env().unwrap();
+        let matched = get_services_by_tag(&services, "database");
+        assert_eq!(1, matched.len());
+        assert_eq!("service_a", matched.first().unwrap().name);
+        env::remove_var(VCAP_SERVICES);
+    }
+
+    #[test]
+    fn test_get_services_by_label() {
+        let json = r#"{
+      "serviceA": [
+        {"name":"service_a", "label": "postgres", "credentials": {"uri": "example_uri"}}
+      ],
+      "serviceB": [
+        {"name":"service_b", "label": "redis", "credentials": {"uri": "other_uri"}}
+      ]
+    }"#;
+        env::set_var(VCAP_SERVICES, json);
+        let services = get_services_from_env().unwrap();
+        let matched = get_services_by_label(&services, "redis");
+        assert_eq!(1, matched.len());
+        assert_eq!("service_b", matched.first().unwrap().name);
+        env::remove_var(VCAP_SERVICES);
+    }
+
+    #[derive(Deserialize)]
+    struct RedisCreds {
+        uri: String,
+        max_connections: u32,
+    }
+
+    #[test]
+    fn test_get_typed_service_cred_from_env() {
+        let json = r#"{
+      "serviceA": [
+        {
+          "name":"service_a",
+          "credentials": {
+            "uri": "example_uri",
+            "max_connections": 10
+          }
+        }
+      ]
+    }"#;
+        env::set_var(VCAP_SERVICES, json);
+        let creds: Vec<RedisCreds> = get_typed_service_cred_from_env("serviceA".to_string()).unwrap();
+        assert_eq!(1, creds.len());
+        let cred = creds.first().unwrap();
+        assert_eq!("example_uri", cred.uri);
+        assert_eq!(10, cred.max_connections);
+        env::remove_var(VCAP_SERVICES);
+    }
+
+    #[test]
+    fn test_get_typed_service_credentials_malformed() {
+        let mut services = HashMap::new();
+        services.insert("serviceA".to_string(), vec![Service {
+            name: "service_a".to_string(),
+            instance_name: "".to_string(),
+            binding_name: "".to_string(),
+            credentials: serde_json::json!({"uri": "example_uri"}),
+            label: "".to_string(),
+            tags: Vec::new(),
+            plan: "".to_string(),
+            provider: "".to_string(),
+        }]);
+        let err = get_typed_service_credentials::<RedisCreds>(services, "serviceA".to_string()).err().unwrap();
+        assert!(matches!(err, CFError::MalformedCredentials(_)));
+    }
+
+    #[test]
+    fn test_get_services_from_str() {
+        let json = r#"{
+      "serviceA": [
+        {
+          "name":"service_a",
+          "credentials": {
+            "uri": "example_uri",
+            "port": 8080
+          }
+        }
+      ]
+    }"#;
+        let services = get_services_from_str(json).unwrap();
+        let service_details = services.get("serviceA").unwrap().first().unwrap();
+        assert_eq!("service_a", service_details.name);
+        assert_eq!("example_uri", service_details.credentials["uri"]);
+    }
+
+    #[test]
+    fn test_get_services_from_file() {
+        let json = r#"{
+      "serviceA": [
+        {
+          "name":"service_a",
+          "credentials": {
+            "uri": "example_uri",
+            "port": 8080
+          }
+        }
+      ]
+    }"#;
+        let path = env::temp_dir().join("cf_services_test_get_services_from_file.json");
+        std::fs::write(&path, json).unwrap();
+        let services = get_services_from_file(&path).unwrap();
+        let service_details = services.get("serviceA").unwrap().first().unwrap();
+        assert_eq!("service_a", service_details.name);
+        assert_eq!("example_uri", service_details.credentials["uri"]);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_get_services_from_file_not_found() {
+        let path = env::temp_dir().join("cf_services_test_file_that_does_not_exist.json");
+        let err = get_services_from_file(&path).err().unwrap();
+        assert!(matches!(err, CFError::IoError(_)));
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_get_services_from_yaml_str() {
+        use crate::get_services_from_yaml_str;
+
+        let yaml = r#"
+serviceA:
+  - name: service_a
+    credentials:
+      uri: example_uri
+      port: 8080
+"#;
+        let services = get_services_from_yaml_str(yaml).unwrap();
+        let service_details = services.get("serviceA").unwrap().first().unwrap();
+        assert_eq!("service_a", service_details.name);
+        assert_eq!("example_uri", service_details.credentials["uri"]);
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_get_services_from_file_yaml() {
+        let yaml = r#"
+serviceA:
+  - name: service_a
+    credentials:
+      uri: example_uri
+      port: 8080
+"#;
+        let path = env::temp_dir().join("cf_services_test_get_services_from_file.yaml");
+        std::fs::write(&path, yaml).unwrap();
+        let services = get_services_from_file(&path).unwrap();
+        let service_details = services.get("serviceA").unwrap().first().unwrap();
+        assert_eq!("service_a", service_details.name);
+        assert_eq!("example_uri", service_details.credentials["uri"]);
+        std::fs::remove_file(&path).unwrap();
+    }
 }
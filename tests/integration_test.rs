@@ -18,7 +18,7 @@ fn integration_test() {
     env::set_var(VCAP_SERVICES, json);
     let creds = get_service_cred_from_env("serviceA".to_string()).unwrap();
     assert_eq!(1, creds.len());
-    let cred = creds.get(0).unwrap();
+    let cred = creds.first().unwrap();
     assert_eq!("example_uri", cred.uri);
     assert_eq!(8080, cred.port);
     env::remove_var(VCAP_SERVICES);